@@ -0,0 +1,184 @@
+use std::io::{self, Read};
+
+/// The character encoding a [ByteStream] believes its buffered bytes are in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Utf8,
+}
+
+/// How sure a [ByteStream] is about its [Encoding].
+///
+/// Bytes outside the ASCII range are only ever *assumed* to be UTF-8 until
+/// they're actually validated, so callers can tell apart a best-effort guess
+/// from a checked fact.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Confidence {
+    Tentative,
+    Certain,
+}
+
+/// An incrementally-growable byte buffer sitting between a socket and the
+/// [Lexer](crate::lexer::Lexer).
+///
+/// Unlike a fixed-size read buffer, a [ByteStream] can be fed more bytes as
+/// they arrive (`read_from`) so a request that spans multiple TCP reads, or
+/// one larger than a single `read` call, doesn't get truncated.
+pub struct ByteStream {
+    buf: Vec<u8>,
+    pos: usize,
+    encoding: Encoding,
+    confidence: Confidence,
+    eof: bool,
+}
+
+impl ByteStream {
+    pub fn new() -> Self {
+        Self {
+            buf: Vec::new(),
+            pos: 0,
+            encoding: Encoding::Utf8,
+            confidence: Confidence::Tentative,
+            eof: false,
+        }
+    }
+
+    /// The encoding a [ByteStream] assumes its buffered bytes are in.
+    pub fn encoding(&self) -> Encoding {
+        self.encoding
+    }
+
+    /// How sure a [ByteStream] is about [Self::encoding], based on the
+    /// non-ASCII bytes it has seen so far.
+    pub fn confidence(&self) -> Confidence {
+        self.confidence
+    }
+
+    /// `true` once the underlying reader has reported end-of-stream.
+    pub fn at_eof(&self) -> bool {
+        self.eof
+    }
+
+    /// The full buffered byte range, including already-consumed bytes, for
+    /// rendering diagnostics against the original request.
+    pub fn buffered(&self) -> &[u8] {
+        &self.buf
+    }
+
+    /// Returns `true` if there are no unconsumed bytes buffered.
+    pub fn is_empty(&self) -> bool {
+        self.pos >= self.buf.len()
+    }
+
+    /// Reads more bytes from `src` and appends them to the buffer, updating
+    /// the encoding confidence as it goes. Returns the number of bytes read;
+    /// `0` means `src` is exhausted.
+    pub fn read_from(&mut self, src: &mut impl Read) -> io::Result<usize> {
+        let mut chunk = [0u8; 4096];
+        let n = src.read(&mut chunk)?;
+
+        if n == 0 {
+            self.eof = true;
+            return Ok(0);
+        }
+
+        self.buf.extend_from_slice(&chunk[..n]);
+
+        // Validate against the whole buffer, not just this chunk: a
+        // multi-byte UTF-8 sequence can straddle two `read` calls, which
+        // would make each half look individually invalid even though the
+        // reassembled bytes are fine.
+        if !self.buf.is_ascii() {
+            self.confidence = match std::str::from_utf8(&self.buf) {
+                Ok(_) => Confidence::Certain,
+                Err(_) => Confidence::Tentative,
+            };
+        }
+
+        Ok(n)
+    }
+
+    /// Returns the next unconsumed byte without advancing past it.
+    pub fn peek(&self) -> Option<&u8> {
+        self.buf.get(self.pos)
+    }
+
+    /// Consumes and returns the next byte.
+    pub fn read_byte(&mut self) -> Option<u8> {
+        let byte = self.buf.get(self.pos).copied();
+        if byte.is_some() {
+            self.pos += 1;
+        }
+        byte
+    }
+
+    /// Reads `n` raw bytes, bypassing HTTP token rules entirely, for things
+    /// like a request body that must not be lexed. Pulls more bytes from
+    /// `src` as needed; returns fewer than `n` only if `src` reaches
+    /// end-of-stream first.
+    pub fn read_n(&mut self, n: usize, src: &mut impl Read) -> io::Result<Vec<u8>> {
+        while self.buf.len() - self.pos < n && !self.eof {
+            if self.read_from(src)? == 0 {
+                break;
+            }
+        }
+
+        let end = (self.pos + n).min(self.buf.len());
+        let bytes = self.buf[self.pos..end].to_vec();
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    /// The current read position, i.e. how many bytes have been consumed.
+    pub fn pos(&self) -> usize {
+        self.pos
+    }
+
+    /// Moves the read position to an arbitrary byte offset, clamped to the
+    /// buffered range. Used to undo the lexer's one-token lookahead when
+    /// handing off to a raw `read_n` (the lookahead may have already
+    /// tokenized one or more bytes past the point the caller wants to
+    /// resume raw reading from).
+    pub fn seek(&mut self, pos: usize) {
+        self.pos = pos.min(self.buf.len());
+    }
+
+    /// Rewinds to the start of the buffer, so a retried parse over the same
+    /// [ByteStream] reads from the beginning again. Consumed bytes are kept;
+    /// [Self::compact] is what drops them.
+    pub fn rewind(&mut self) {
+        self.seek(0);
+    }
+
+    /// Discards bytes already consumed up to the current position, keeping
+    /// only the buffered bytes a pipelined next request still needs.
+    pub fn compact(&mut self) {
+        if self.pos > 0 {
+            self.buf.drain(..self.pos);
+            self.pos = 0;
+        }
+    }
+}
+
+impl Default for ByteStream {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ByteStream, Confidence};
+
+    #[test]
+    fn confidence_is_certain_even_when_a_multibyte_sequence_spans_reads() {
+        let mut stream = ByteStream::new();
+        // "é" is 0xC3 0xA9; split the two bytes across two separate
+        // `read_from` calls so each one looks like a lone invalid UTF-8
+        // byte on its own.
+        stream.read_from(&mut &[0xC3u8][..]).unwrap();
+        assert_eq!(stream.confidence(), Confidence::Tentative);
+
+        stream.read_from(&mut &[0xA9u8][..]).unwrap();
+        assert_eq!(stream.confidence(), Confidence::Certain);
+    }
+}
@@ -4,14 +4,32 @@ use std::{collections::HashMap, fmt::Debug};
 #[derive(Debug)]
 pub enum HttpMethod {
     GET,
+    HEAD,
     POST,
+    PUT,
+    DELETE,
+    CONNECT,
+    OPTIONS,
+    TRACE,
+    PATCH,
     BAD,
 }
 
-/// An enum containing the supported http versions.
-#[derive(Debug)]
-pub enum HttpVsn {
-    HTTP1_1,
+/// An http version, e.g. `HTTP/1.1` is `{ major: 1, minor: 1 }`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HttpVsn {
+    pub major: u8,
+    pub minor: u8,
+}
+
+impl HttpVsn {
+    /// Whether a connection should default to persistent ("keep-alive")
+    /// when the request doesn't say either way. HTTP/1.1+ defaults to
+    /// keep-alive; HTTP/1.0 and earlier default to closing after one
+    /// response.
+    pub fn default_keep_alive(&self) -> bool {
+        (self.major, self.minor) >= (1, 1)
+    }
 }
 
 /// The http structure.
@@ -19,7 +37,9 @@ pub struct HttpEntity {
     pub http_version: HttpVsn,
     pub method: HttpMethod,
     pub path: String,
+    pub query: HashMap<String, String>,
     pub headers: HashMap<String, String>,
+    pub body: Vec<u8>,
 }
 
 impl Debug for HttpEntity {
@@ -28,7 +48,26 @@ impl Debug for HttpEntity {
             .field("http_version", &self.http_version)
             .field("method", &self.method)
             .field("path", &self.path)
+            .field("query", &self.query)
             .field("headers", &self.headers)
+            .field("body", &self.body)
             .finish()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::HttpVsn;
+
+    #[test]
+    fn http_1_1_and_later_default_to_keep_alive() {
+        assert!(HttpVsn { major: 1, minor: 1 }.default_keep_alive());
+        assert!(HttpVsn { major: 2, minor: 0 }.default_keep_alive());
+    }
+
+    #[test]
+    fn http_1_0_and_earlier_default_to_close() {
+        assert!(!HttpVsn { major: 1, minor: 0 }.default_keep_alive());
+        assert!(!HttpVsn { major: 0, minor: 9 }.default_keep_alive());
+    }
+}
@@ -1,4 +1,12 @@
-use std::iter::Peekable;
+use crate::byte_stream::ByteStream;
+
+/// A lexed token paired with the byte span (start..end) it occupies in the
+/// original request buffer.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub span: (usize, usize),
+}
 
 #[derive(Debug, PartialEq, Eq)]
 pub enum TokenKind {
@@ -57,12 +65,18 @@ pub enum DelimiterKind {
 }
 
 pub struct Lexer<'a> {
-    peekable: &'a mut Peekable<std::slice::Iter<'a, u8>>,
+    stream: &'a mut ByteStream,
 }
 
 impl<'a> Lexer<'a> {
-    pub fn new(peekable: &'a mut Peekable<std::slice::Iter<'a, u8>>) -> Self {
-        Self { peekable }
+    pub fn new(stream: &'a mut ByteStream) -> Self {
+        Self { stream }
+    }
+
+    /// Exposes the underlying [ByteStream], e.g. for a raw `read_n` that
+    /// must bypass HTTP token rules entirely (a request body).
+    pub fn stream_mut(&mut self) -> &mut ByteStream {
+        self.stream
     }
 }
 
@@ -103,11 +117,23 @@ impl Tokens for u8 {
 }
 
 impl Lexer<'_> {
+    /// Reads a token together with the byte span it occupies in the input.
+    pub fn lex_token(&mut self) -> Token {
+        let start = self.stream.pos();
+        let kind = self.lex();
+        let end = self.stream.pos();
+
+        Token {
+            kind,
+            span: (start, end),
+        }
+    }
+
     /// Reads a token.
     pub fn lex(&mut self) -> TokenKind {
         use TokenKind::*;
 
-        if let Some(u) = self.peekable.peek() {
+        if let Some(u) = self.stream.peek() {
             match u {
                 b'\0' => self.just(Eof),
                 b' ' => self.just(Space),
@@ -131,24 +157,26 @@ impl Lexer<'_> {
                 b'"' => self.just(DQuote),
                 b':' => self.just(Delimiter(DelimiterKind::Colon)),
                 b'/' => self.just(Delimiter(DelimiterKind::Slash)),
+                b'?' => self.just(Delimiter(DelimiterKind::QuestionMark)),
+                b'=' => self.just(Delimiter(DelimiterKind::Equal)),
 
                 b'\r' => {
                     self.eat();
-                    match self.peekable.peek() {
-                        Some(&&b'\n') => self.just(CRLF),
+                    match self.stream.peek() {
+                        Some(&b'\n') => self.just(CRLF),
                         _ => self.just(CR),
                     }
                 }
                 b'\n' => self.just(LF),
 
                 u if u.is_ascii_digit() => self.digit(),
-                u if u.is_ascii_alphanumeric() => match self.peekable.peek() {
-                    Some(v) if v.is_ascii_alphanumeric() => self.accu_token(),
-                    Some(_) | None => {
-                        let a = char::from(*self.eat().unwrap());
-                        self.just(Char(TCharKind::Alpha(a)))
+                u if u.is_ascii_alphanumeric() => {
+                    let a = self.eat().unwrap();
+                    match self.stream.peek() {
+                        Some(v) if v.is_tkn() && *v != b'%' && *v != b'&' => self.accu_token(a),
+                        _ => Char(TCharKind::Alpha(char::from(a))),
                     }
-                },
+                }
                 _ => self.just(TokenKind::Bad),
             }
         } else {
@@ -156,9 +184,9 @@ impl Lexer<'_> {
         }
     }
 
-    /// Eats the peekable's current item.
-    pub fn eat(&mut self) -> Option<&u8> {
-        self.peekable.next()
+    /// Eats the stream's current byte.
+    pub fn eat(&mut self) -> Option<u8> {
+        self.stream.read_byte()
     }
 
     /// Eats the current item and returns the `tk`.
@@ -167,14 +195,19 @@ impl Lexer<'_> {
         tk
     }
 
-    /// Accumulates a sequence of bytes and returns a [TokenKind::Token].
-    fn accu_token(&mut self) -> TokenKind {
+    /// Accumulates a sequence of bytes, starting with the already-consumed
+    /// `first` byte, and returns a [TokenKind::Token].
+    ///
+    /// Stops at a `%` or `&`, even though both are [Tokens::is_tchar] bytes,
+    /// so a percent-escape and a query-string separator are always their
+    /// own token instead of being folded into whatever token preceded them.
+    fn accu_token(&mut self, first: u8) -> TokenKind {
         let mut seq = String::new();
-        while let Some(u) = self.peekable.peek() {
-            if u.is_tkn() {
+        seq.push(char::from(first));
+        while let Some(u) = self.stream.peek() {
+            if u.is_tkn() && *u != b'%' && *u != b'&' {
                 let c = self.eat().unwrap();
-                let c = char::from(*c);
-                seq.push(c);
+                seq.push(char::from(c));
             } else {
                 break;
             }
@@ -187,20 +220,20 @@ impl Lexer<'_> {
         let mut digit = String::new();
         {
             let c = self.eat();
-            digit.push(char::from(*c.unwrap()));
+            digit.push(char::from(c.unwrap()));
         }
         TokenKind::Char(TCharKind::Digit(digit.parse().unwrap()))
     }
 }
 
 impl Iterator for Lexer<'_> {
-    type Item = TokenKind;
+    type Item = Token;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let t = self.lex();
-        match t {
+        let t = self.lex_token();
+        match t.kind {
             TokenKind::Eof => None,
-            other => Some(other),
+            _ => Some(t),
         }
     }
 }
@@ -1,45 +1,112 @@
-pub mod lexer;
+pub mod byte_stream;
 pub mod http_entity;
+pub mod lexer;
 pub mod parser;
+pub mod response;
 
 use std::{
-    io::Read,
-    iter::Peekable,
-    net::{TcpListener, TcpStream}, result,
+    net::{Shutdown, TcpListener, TcpStream},
+    result,
 };
 
-use lexer::TokenKind;
-
-use crate::parser::Parser;
-
-const BUFFER_MAX_SIZE: usize = 65535;
+use byte_stream::ByteStream;
+use http_entity::HttpEntity;
+use parser::{header, Parser};
+use response::{Handler, HttpResponse};
 
 type Result = result::Result<(), std::io::Error>;
 
 fn main() -> Result {
     println!("Hello, world!");
     let listener = TcpListener::bind("0.0.0.0:8080")?;
+    let handler = EchoHandler;
 
     for stream in listener.incoming() {
-        handle(stream.unwrap())?;
+        handle(stream.unwrap(), &handler)?;
     }
 
     Ok(())
 }
 
-fn handle(mut stream: TcpStream) -> Result {
-    use std::net::Shutdown;
+/// Echoes the parsed request back as the response body; just enough of a
+/// [Handler] to exercise the request/response loop end to end.
+struct EchoHandler;
 
-    let mut buffer = [0u8; BUFFER_MAX_SIZE];
-    let size = stream.read(&mut buffer)?;
+impl Handler for EchoHandler {
+    fn handle(&self, entity: &HttpEntity) -> HttpResponse {
+        HttpResponse::ok().body(format!("{:#?}", entity))
+    }
+}
+
+/// Serves requests off one connection until the client closes it or a
+/// request asks to close (`Connection: close` on 1.1, the absence of
+/// `Connection: keep-alive` on 1.0), handing each parsed request to
+/// `handler` and writing back whatever [HttpResponse] it returns.
+fn handle(mut stream: TcpStream, handler: &impl Handler) -> Result {
+    let mut byte_stream = ByteStream::new();
+    byte_stream.read_from(&mut stream)?;
 
-    let peekable: &mut Peekable<std::slice::Iter<'_, u8>> = &mut buffer[..size].iter().peekable();
+    loop {
+        if byte_stream.is_empty() && byte_stream.at_eof() {
+            break;
+        }
 
-    let mut parser = Parser::new(peekable);
-    let request = parser.request_line();
+        let entity = match next_request(&mut byte_stream, &mut stream)? {
+            Some(entity) => entity,
+            None => break,
+        };
 
-    println!("request {:#?}", request);
+        let response = handler.handle(&entity);
+        response.write_to(&mut stream)?;
+
+        // Keep whatever bytes belong to a pipelined next request instead of
+        // discarding the buffer between requests.
+        byte_stream.compact();
+
+        if !wants_keep_alive(&entity) {
+            break;
+        }
+    }
 
     stream.shutdown(Shutdown::Both)?;
     Ok(())
 }
+
+/// Parses the next [HttpEntity] off `byte_stream`, pulling more bytes from
+/// `stream` as needed. Returns `None` once the client has closed the
+/// connection without leaving behind another full request.
+fn next_request(
+    byte_stream: &mut ByteStream,
+    stream: &mut TcpStream,
+) -> result::Result<Option<HttpEntity>, std::io::Error> {
+    loop {
+        byte_stream.rewind();
+        let mut parser = Parser::new(byte_stream);
+
+        match parser.request(stream) {
+            Ok(entity) => return Ok(Some(entity)),
+            // The request may simply not have arrived yet; pull more bytes
+            // off the wire and retry from the top of the buffer.
+            Err(err) if err.incomplete && !byte_stream.at_eof() => {
+                if byte_stream.read_from(stream)? == 0 {
+                    return Ok(None);
+                }
+            }
+            Err(err) => {
+                eprintln!("{}", err.render(byte_stream.buffered()));
+                return Ok(None);
+            }
+        }
+    }
+}
+
+/// Whether the connection should stay open for another request, honoring
+/// an explicit `Connection` header and otherwise falling back to the http
+/// version's default.
+fn wants_keep_alive(entity: &HttpEntity) -> bool {
+    match header(&entity.headers, "Connection") {
+        Some(v) if v.eq_ignore_ascii_case("close") => false,
+        Some(v) if v.eq_ignore_ascii_case("keep-alive") => true,
+        _ => entity.http_version.default_keep_alive(),
+    }
+}
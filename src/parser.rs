@@ -1,25 +1,86 @@
 use std::collections::HashMap;
-use std::iter::Peekable;
+use std::io::Read;
 use std::mem::{self};
 
+use crate::byte_stream::ByteStream;
 use crate::http_entity::{self, HttpEntity};
-use crate::lexer::{DelimiterKind, Lexer, TCharKind, TokenKind};
+use crate::lexer::{DelimiterKind, Lexer, TCharKind, Token, TokenKind};
 
+/// A parse error, carrying the byte span of the offending token so callers
+/// can point diagnostics at the exact bytes that triggered it.
 #[derive(Debug)]
-pub struct ParseErr(String);
+pub struct ParseErr {
+    pub span: (usize, usize),
+    pub message: String,
+    /// `true` when the error was caused by running out of buffered bytes
+    /// rather than a genuinely malformed request, so callers can tell a
+    /// "read more and retry" condition apart from a real syntax error.
+    pub incomplete: bool,
+}
+
+impl ParseErr {
+    fn new(span: (usize, usize), message: impl Into<String>) -> Self {
+        Self {
+            span,
+            message: message.into(),
+            incomplete: false,
+        }
+    }
+
+    fn incomplete(span: (usize, usize), message: impl Into<String>) -> Self {
+        Self {
+            span,
+            message: message.into(),
+            incomplete: true,
+        }
+    }
+
+    /// Renders the error with the offending bytes of `src` underlined by a
+    /// caret row, e.g.:
+    ///
+    /// ```text
+    /// Expected Space, found Delimiter(Slash)
+    /// GET/items HTTP/1.1
+    ///    ^
+    /// ```
+    pub fn render(&self, src: &[u8]) -> String {
+        let (start, end) = (self.span.0.min(src.len()), self.span.1.min(src.len()));
+
+        let line_start = src[..start]
+            .iter()
+            .rposition(|&b| b == b'\n')
+            .map_or(0, |i| i + 1);
+        let line_end = src[end..]
+            .iter()
+            .position(|&b| b == b'\n')
+            .map_or(src.len(), |i| end + i);
+
+        let line = String::from_utf8_lossy(&src[line_start..line_end]);
+        let caret_offset = start - line_start;
+        let caret_len = (end - start).max(1);
+
+        format!(
+            "{}\n{}\n{}{}",
+            self.message,
+            line,
+            " ".repeat(caret_offset),
+            "^".repeat(caret_len)
+        )
+    }
+}
 
 pub struct Parser<'a> {
     pub lexer: Lexer<'a>,
-    pub curr: TokenKind,
-    pub next: TokenKind,
+    pub curr: Token,
+    pub next: Token,
 }
 
 impl<'a> Parser<'a> {
-    pub fn new(peekable: &'a mut Peekable<std::slice::Iter<'a, u8>>) -> Self {
-        let mut lexer = Lexer::new(peekable);
+    pub fn new(stream: &'a mut ByteStream) -> Self {
+        let mut lexer = Lexer::new(stream);
 
-        let curr = lexer.lex();
-        let next = lexer.lex();
+        let curr = lexer.lex_token();
+        let next = lexer.lex_token();
 
         Self { lexer, curr, next }
     }
@@ -28,9 +89,10 @@ impl<'a> Parser<'a> {
 pub type ParseResult = Result<TokenKind, ParseErr>;
 
 impl Parser<'_> {
-    /// Advances the state of the [Lexer].
-    fn walk(&mut self) -> TokenKind {
-        let mut a_next = self.lexer.lex();
+    /// Advances the state of the [Lexer], returning the token that was
+    /// current before the walk.
+    fn walk(&mut self) -> Token {
+        let mut a_next = self.lexer.lex_token();
 
         mem::swap(&mut self.next, &mut self.curr);
         mem::swap(&mut self.next, &mut a_next);
@@ -38,29 +100,54 @@ impl Parser<'_> {
         a_next
     }
 
+    /// Builds a [ParseErr] at `span`, marking it [ParseErr::incomplete] when
+    /// the current token ran off the end of the buffered bytes rather than
+    /// being genuinely malformed.
+    fn err(&self, span: (usize, usize), message: impl Into<String>) -> ParseErr {
+        match self.curr.kind {
+            TokenKind::Eof => ParseErr::incomplete(span, message),
+            _ => ParseErr::new(span, message),
+        }
+    }
+
+    /// Like [Self::err], but classifies incompleteness from `tk` itself
+    /// rather than `self.curr`. Callers that `walk()` the token that might
+    /// be the failure before checking it have already moved `self.curr` on
+    /// to the *next* token by the time the error is built, so `self.err`
+    /// would classify incompleteness off the wrong token entirely.
+    fn err_at(&self, tk: &Token, span: (usize, usize), message: impl Into<String>) -> ParseErr {
+        match tk.kind {
+            TokenKind::Eof => ParseErr::incomplete(span, message),
+            _ => ParseErr::new(span, message),
+        }
+    }
+
     /// Returns [Ok(String)] if the current token is a [TokenKind::Token].
     fn expect_token(&mut self) -> Result<String, ParseErr> {
-        match &self.curr {
+        match &self.curr.kind {
             TokenKind::Token(s) => {
                 let s = s.clone();
                 self.walk();
                 Ok(s)
             }
-            _ => Err(ParseErr("Was expected a Token".into())),
+            _ => Err(self.err(self.curr.span, "Was expected a Token")),
         }
     }
 
     /// Expects the current token to be equal to `tk` and advances the parser state.
     pub fn expect(&mut self, tk: TokenKind) -> ParseResult {
-        match &self.curr {
-            t if *t == tk => Ok(self.walk()),
-            other => Err(ParseErr(format!("Expected {:?}, found {:?}", tk, other))),
+        match &self.curr.kind {
+            t if *t == tk => Ok(self.walk().kind),
+            other => Err(self.err(
+                self.curr.span,
+                format!("Expected {:?}, found {:?}", tk, other),
+            )),
         }
     }
 
     /// Returns `true` if the current token is equal to `tk`.
     pub fn curr_is(&self, tk: TokenKind) -> bool {
-        match &self.curr {
+        match &self.curr.kind {
             t if *t == tk => true,
             _ => false,
         }
@@ -80,44 +167,167 @@ impl Parser<'_> {
         match self.expect_token() {
             Ok(s) => match s.as_str() {
                 "GET" => http_entity::HttpMethod::GET,
+                "HEAD" => http_entity::HttpMethod::HEAD,
                 "POST" => http_entity::HttpMethod::POST,
+                "PUT" => http_entity::HttpMethod::PUT,
+                "DELETE" => http_entity::HttpMethod::DELETE,
+                "CONNECT" => http_entity::HttpMethod::CONNECT,
+                "OPTIONS" => http_entity::HttpMethod::OPTIONS,
+                "TRACE" => http_entity::HttpMethod::TRACE,
+                "PATCH" => http_entity::HttpMethod::PATCH,
                 _ => http_entity::HttpMethod::BAD,
             },
             Err(_) => http_entity::HttpMethod::BAD,
         }
     }
 
-    /// Reads recursively a http path.
-    pub fn path(&mut self, s: &mut String) -> Result<String, ParseErr> {
-        match self.walk() {
-            TokenKind::DQuote => todo!(),
-            TokenKind::Space | TokenKind::CRLF | TokenKind::Eof => Ok(s.clone()),
-            TokenKind::Bad => Err(ParseErr("Bad token found".into())),
-            tk => {
-                s.push_str(&tk.to_string());
-                self.path(s)
+    /// Parses the request-target per RFC 3986: everything up to the first
+    /// `?` is the path, the rest is the query string. `%XX` escapes are
+    /// percent-decoded as they're read; the decoded bytes are collected and
+    /// assembled into a `String` at the end, rather than one `char` at a
+    /// time, so a multi-byte UTF-8 sequence split across several `%XX`
+    /// escapes (e.g. `%C3%A9`) is reassembled correctly instead of being
+    /// reinterpreted as separate Unicode scalar values.
+    pub fn request_target(&mut self) -> Result<(String, HashMap<String, String>), ParseErr> {
+        let start = self.curr.span.0;
+        let mut path = Vec::new();
+        let mut query = HashMap::new();
+
+        let end = loop {
+            let tk = self.walk();
+            match tk.kind {
+                TokenKind::DQuote => {
+                    return Err(ParseErr::new(tk.span, "Unexpected '\"' in request-target"))
+                }
+                TokenKind::Space | TokenKind::CRLF | TokenKind::Eof => break tk.span.1,
+                TokenKind::Bad => return Err(ParseErr::new(tk.span, "Bad token found")),
+                TokenKind::Delimiter(DelimiterKind::QuestionMark) => {
+                    self.query(&mut query)?;
+                    break tk.span.1;
+                }
+                TokenKind::Char(TCharKind::Percent) => path.push(self.percent_escape(tk.span)?),
+                kind => path.extend_from_slice(kind.to_string().as_bytes()),
+            }
+        };
+
+        let path = String::from_utf8(path)
+            .map_err(|_| ParseErr::new((start, end), "Request-target path is not valid UTF-8"))?;
+
+        Ok((path, query))
+    }
+
+    /// Parses a `key=value&key=value` query string into `query`, stopping
+    /// at the end of the request-target.
+    fn query(&mut self, query: &mut HashMap<String, String>) -> Result<(), ParseErr> {
+        let mut key: Vec<u8> = Vec::new();
+        let mut val: Vec<u8> = Vec::new();
+        let mut in_val = false;
+
+        loop {
+            let tk = self.walk();
+            match tk.kind {
+                TokenKind::DQuote => {
+                    return Err(ParseErr::new(tk.span, "Unexpected '\"' in query string"))
+                }
+                TokenKind::Space | TokenKind::CRLF | TokenKind::Eof => {
+                    if !key.is_empty() {
+                        let k = decode_utf8_component(mem::take(&mut key), tk.span)?;
+                        let v = decode_utf8_component(mem::take(&mut val), tk.span)?;
+                        query.insert(k, v);
+                    }
+                    break;
+                }
+                TokenKind::Bad => return Err(ParseErr::new(tk.span, "Bad token found")),
+                // The first `=` is the key/value separator; any further `=`
+                // inside the value (e.g. `expr=x=y`) is a literal byte, not
+                // another separator.
+                TokenKind::Delimiter(DelimiterKind::Equal) => {
+                    if in_val {
+                        val.push(b'=');
+                    } else {
+                        in_val = true;
+                    }
+                }
+                TokenKind::Char(TCharKind::And) => {
+                    let k = decode_utf8_component(mem::take(&mut key), tk.span)?;
+                    let v = decode_utf8_component(mem::take(&mut val), tk.span)?;
+                    query.insert(k, v);
+                    in_val = false;
+                }
+                TokenKind::Char(TCharKind::Percent) => {
+                    let decoded = self.percent_escape(tk.span)?;
+                    if in_val { val.push(decoded) } else { key.push(decoded) }
+                }
+                kind => {
+                    let bytes = kind.to_string().into_bytes();
+                    if in_val {
+                        val.extend_from_slice(&bytes)
+                    } else {
+                        key.extend_from_slice(&bytes)
+                    }
+                }
             }
         }
+
+        Ok(())
+    }
+
+    /// Reads a `%XX` escape (the `%` has already been consumed) and returns
+    /// the decoded byte, erroring with the span of the whole escape if
+    /// either hex digit is missing or invalid.
+    fn percent_escape(&mut self, percent_span: (usize, usize)) -> Result<u8, ParseErr> {
+        let hi = self.hex_digit(percent_span)?;
+        let lo = self.hex_digit(percent_span)?;
+        Ok(hi * 16 + lo)
     }
 
-    /// Parses a http 1.1 version.
-    pub fn http_1_1(&mut self) -> Result<http_entity::HttpVsn, ParseErr> {
-        use TokenKind::*;
+    fn hex_digit(&mut self, percent_span: (usize, usize)) -> Result<u8, ParseErr> {
+        let tk = self.walk();
+        let span = (percent_span.0, tk.span.1);
 
-        let http = self.expect_token()?;
+        let digit = match &tk.kind {
+            TokenKind::Char(TCharKind::Digit(d)) => Some(*d),
+            TokenKind::Char(TCharKind::Alpha(c)) => c.to_digit(16).map(|d| d as u8),
+            // A hex letter followed by another word character lexes as the
+            // start of a multi-char Token (`%ab` gives "ab"): take its first
+            // char as the digit and re-lex from the byte right after it.
+            TokenKind::Token(s) => {
+                let d = s.chars().next().and_then(|c| c.to_digit(16)).map(|d| d as u8);
+                if d.is_some() {
+                    self.lexer.stream_mut().seek(tk.span.0 + 1);
+                    self.resync();
+                }
+                d
+            }
+            _ => None,
+        };
+
+        digit.ok_or_else(|| self.err_at(&tk, span, "Invalid percent-encoding: expected a hex digit"))
+    }
 
-        println!("{:?}", &http);
-        if !(http == "HTTP") {
-            return Err(ParseErr("Expected 'HTTP' token".into()));
+    /// Parses an http version of the form `HTTP/<major>.<minor>`.
+    pub fn version(&mut self) -> Result<http_entity::HttpVsn, ParseErr> {
+        let span = self.curr.span;
+        let http = self.expect_token()?;
+        if http != "HTTP" {
+            return Err(self.err(span, "Expected 'HTTP' token"));
         }
 
-        let _slash = self.expect(Delimiter(DelimiterKind::Slash))?;
+        self.expect(TokenKind::Delimiter(DelimiterKind::Slash))?;
+        let major = self.version_digit()?;
+        self.expect(TokenKind::Char(TCharKind::Dot))?;
+        let minor = self.version_digit()?;
 
-        let _one = self.expect(Char(TCharKind::Digit(1)))?;
-        let _dot = self.expect(Char(TCharKind::Dot))?;
-        let _one = self.expect(Char(TCharKind::Digit(1)))?;
+        Ok(http_entity::HttpVsn { major, minor })
+    }
 
-        Ok(http_entity::HttpVsn::HTTP1_1)
+    /// Reads a single version component digit (`HTTP/1.1`'s `1`s).
+    fn version_digit(&mut self) -> Result<u8, ParseErr> {
+        let tk = self.walk();
+        match tk.kind {
+            TokenKind::Char(TCharKind::Digit(d)) => Ok(d),
+            _ => Err(self.err_at(&tk, tk.span, "Expected a version digit")),
+        }
     }
 
     /// Parses a http request line.
@@ -130,11 +340,11 @@ impl Parser<'_> {
     pub fn request_line(&mut self) -> Result<RequestLine, ParseErr> {
         let method = self.method();
         self.expect(TokenKind::Space)?;
-        let path = self.path(&mut String::new())?;
-        let http_version = self.http_1_1()?;
+        let (path, query) = self.request_target()?;
+        let http_version = self.version()?;
         self.expect(TokenKind::CRLF)?;
 
-        Ok(RequestLine(method, path, http_version))
+        Ok(RequestLine(method, path, query, http_version))
     }
 
     pub fn headers(&mut self) -> Result<HttpEntity, ParseErr> {
@@ -148,10 +358,39 @@ impl Parser<'_> {
             self.opt_space();
 
             let mut val = String::new();
+            let mut bad_run: Vec<u8> = Vec::new();
 
             while !self.curr_is(TokenKind::CRLF) {
-                let str_tk = self.walk().to_string();
-                val.push_str(&str_tk);
+                let tk = self.walk();
+                match tk.kind {
+                    // A header-value byte outside the request's t-char/
+                    // delimiter vocabulary (e.g. a raw non-ASCII byte in a
+                    // UTF-8-encoded filename) lexes as Bad rather than a
+                    // displayable token, one byte at a time; a multi-byte
+                    // UTF-8 sequence therefore shows up as several
+                    // consecutive Bad tokens. Buffer the whole run and
+                    // decode it together instead of byte-by-byte, so a
+                    // split sequence (e.g. "café"'s 0xC3 0xA9) is
+                    // reassembled correctly instead of each byte being an
+                    // incomplete sequence on its own.
+                    TokenKind::Eof => {
+                        return Err(self.err_at(&tk, tk.span, "Unexpected end of header value"))
+                    }
+                    TokenKind::Bad => {
+                        bad_run.extend_from_slice(
+                            &self.lexer.stream_mut().buffered()[tk.span.0..tk.span.1],
+                        );
+                    }
+                    kind => {
+                        if !bad_run.is_empty() {
+                            val.push_str(&self.decode_bad_run(mem::take(&mut bad_run)));
+                        }
+                        val.push_str(&kind.to_string());
+                    }
+                }
+            }
+            if !bad_run.is_empty() {
+                val.push_str(&self.decode_bad_run(mem::take(&mut bad_run)));
             }
 
             self.expect(TokenKind::CRLF)?;
@@ -161,34 +400,427 @@ impl Parser<'_> {
 
         let http_entity = HttpEntity {
             method: request_line.0,
-            http_version: request_line.2,
+            http_version: request_line.3,
             path: request_line.1,
+            query: request_line.2,
             headers,
+            body: Vec::new(),
         };
         Ok(http_entity)
     }
+
+    /// Parses a full request: the request line, headers, and, per
+    /// `Content-Length` or chunked `Transfer-Encoding`, the body. `src` is
+    /// the same reader the [ByteStream] was fed from; body bytes are read
+    /// straight off it, bypassing HTTP token rules.
+    ///
+    /// Leaves the stream positioned exactly at the end of this request (no
+    /// further than it, no lookahead overshoot), so a pipelined next
+    /// request's bytes are left untouched for the next [Parser].
+    pub fn request(&mut self, src: &mut impl Read) -> Result<HttpEntity, ParseErr> {
+        let mut entity = self.headers()?;
+
+        // `headers` leaves `curr` sitting on the blank-line CRLF that
+        // separates headers from the body.
+        self.seek_past_crlf()?;
+        entity.body = self.body(&entity.headers, src)?;
+
+        // No `resync()` here: nothing after this reads `curr`/`next` again
+        // before `entity` is returned, and resyncing would lex ahead into a
+        // pipelined next request, overshooting the stream position past the
+        // true end of this one.
+        Ok(entity)
+    }
+
+    /// Reads the request body according to `headers`, returning an empty
+    /// body if neither `Content-Length` nor chunked `Transfer-Encoding` is
+    /// present.
+    fn body(
+        &mut self,
+        headers: &HashMap<String, String>,
+        src: &mut impl Read,
+    ) -> Result<Vec<u8>, ParseErr> {
+        let content_length = header(headers, "Content-Length");
+        let chunked = header(headers, "Transfer-Encoding")
+            .map(|v| v.eq_ignore_ascii_case("chunked"))
+            .unwrap_or(false);
+
+        // RFC 7230 §3.3.3: a request carrying both headers is ambiguous
+        // about where it ends, and a front-end/back-end pair that each
+        // honor a different one can be made to disagree about where one
+        // request stops and the next begins. Reject outright rather than
+        // picking a side.
+        if content_length.is_some() && chunked {
+            return Err(ParseErr::new(
+                self.curr.span,
+                "Request has both Content-Length and chunked Transfer-Encoding",
+            ));
+        }
+
+        if let Some(len) = content_length {
+            let len: usize = len
+                .trim()
+                .parse()
+                .map_err(|_| self.err(self.curr.span, "Invalid Content-Length"))?;
+            let bytes = self
+                .lexer
+                .stream_mut()
+                .read_n(len, src)
+                .map_err(|e| self.io_err(e))?;
+
+            // `read_n` returns fewer than `len` bytes only once the source
+            // has hit EOF, i.e. the connection closed (or the client lied
+            // about `Content-Length`) before the declared body arrived.
+            if bytes.len() != len {
+                return Err(ParseErr::new(
+                    self.curr.span,
+                    format!(
+                        "Content-Length declared {len} bytes but only {} arrived before EOF",
+                        bytes.len()
+                    ),
+                ));
+            }
+
+            return Ok(bytes);
+        }
+
+        if chunked {
+            return self.chunked_body(src);
+        }
+
+        Ok(Vec::new())
+    }
+
+    /// Reads a chunked body: `<hex-size>\r\n<bytes>\r\n` chunks terminated
+    /// by a zero-size chunk, followed by optional trailer headers and the
+    /// final CRLF.
+    fn chunked_body(&mut self, src: &mut impl Read) -> Result<Vec<u8>, ParseErr> {
+        // `request()`'s `seek_past_crlf()` rewound the raw stream to the
+        // start of the body, but left `curr`/`next` holding whatever the
+        // two-token lookahead had already lexed past that point while
+        // parsing headers. `chunk_size()` below walks `curr`/`next`
+        // directly, so without re-priming them here it reads stale tokens
+        // that don't line up with where the raw stream now is, desyncing
+        // the two the moment a chunk-size token spans more than one byte.
+        self.resync();
+
+        let mut body = Vec::new();
+
+        loop {
+            let size = self.chunk_size()?;
+            self.seek_past_crlf()?;
+
+            let bytes = self
+                .lexer
+                .stream_mut()
+                .read_n(size, src)
+                .map_err(|e| self.io_err(e))?;
+            body.extend_from_slice(&bytes);
+            self.resync();
+
+            if size == 0 {
+                break;
+            }
+
+            self.expect(TokenKind::CRLF)?;
+        }
+
+        while !self.curr_is(TokenKind::CRLF) {
+            let _name = self.expect_token()?;
+            self.expect(TokenKind::Delimiter(DelimiterKind::Colon))?;
+            self.opt_space();
+
+            while !self.curr_is(TokenKind::CRLF) {
+                let tk = self.walk();
+                if tk.kind == TokenKind::Eof {
+                    return Err(self.err_at(&tk, tk.span, "Unexpected end of trailer"));
+                }
+            }
+
+            self.expect(TokenKind::CRLF)?;
+        }
+
+        // This is the true end of the request: seek back to right after
+        // this CRLF so the two-token lookahead doesn't leave the stream
+        // positioned inside a pipelined next request.
+        self.seek_past_crlf()?;
+
+        Ok(body)
+    }
+
+    /// Reads a chunk-size line (hex digits, optionally followed by
+    /// `;extension`s) up to, but not including, its terminating CRLF.
+    fn chunk_size(&mut self) -> Result<usize, ParseErr> {
+        let start = self.curr.span;
+        let mut s = String::new();
+
+        while !self.curr_is(TokenKind::CRLF) {
+            let tk = self.walk();
+            match tk.kind {
+                TokenKind::Eof => return Err(self.err(tk.span, "Unexpected end of chunked body")),
+                TokenKind::Bad => return Err(ParseErr::new(tk.span, "Bad token found")),
+                kind => s.push_str(&kind.to_string()),
+            }
+        }
+
+        let hex = s.split(';').next().unwrap_or("").trim();
+        usize::from_str_radix(hex, 16).map_err(|_| ParseErr::new(start, "Invalid chunk size"))
+    }
+
+    /// Consumes the CRLF `curr` is sitting on and rewinds the stream back
+    /// to right after it, undoing whatever the one-token lookahead had
+    /// already tokenized past that point. Returns that position.
+    fn seek_past_crlf(&mut self) -> Result<usize, ParseErr> {
+        let pos = self.curr.span.1;
+        self.expect(TokenKind::CRLF)?;
+        self.lexer.stream_mut().seek(pos);
+        Ok(pos)
+    }
+
+    /// Re-primes the two-token lookahead after a raw stream read, so normal
+    /// tokenization can resume right where the raw read left off.
+    fn resync(&mut self) {
+        self.curr = self.lexer.lex_token();
+        self.next = self.lexer.lex_token();
+    }
+
+    fn io_err(&self, e: std::io::Error) -> ParseErr {
+        self.err(self.curr.span, format!("I/O error: {}", e))
+    }
+
+    /// Decodes a run of raw bytes that lexed as consecutive [TokenKind::Bad]
+    /// tokens, trusting a full UTF-8 reinterpretation only once the
+    /// [ByteStream] has actually validated the surrounding bytes as such.
+    fn decode_bad_run(&mut self, raw: Vec<u8>) -> String {
+        match self.lexer.stream_mut().confidence() {
+            crate::byte_stream::Confidence::Certain => String::from_utf8_lossy(&raw).into_owned(),
+            crate::byte_stream::Confidence::Tentative => {
+                char::REPLACEMENT_CHARACTER.to_string().repeat(raw.len())
+            }
+        }
+    }
+}
+
+/// Assembles a percent-decoded query key/value from its raw bytes, erroring
+/// with `span` if they don't form valid UTF-8.
+fn decode_utf8_component(bytes: Vec<u8>, span: (usize, usize)) -> Result<String, ParseErr> {
+    String::from_utf8(bytes).map_err(|_| ParseErr::new(span, "Query string is not valid UTF-8"))
+}
+
+/// Case-insensitive header lookup; HTTP header names aren't case-sensitive.
+pub(crate) fn header<'h>(headers: &'h HashMap<String, String>, name: &str) -> Option<&'h str> {
+    headers
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case(name))
+        .map(|(_, v)| v.as_str())
 }
 
 #[derive(Debug)]
-pub struct RequestLine(http_entity::HttpMethod, String, http_entity::HttpVsn);
+pub struct RequestLine(
+    http_entity::HttpMethod,
+    String,
+    HashMap<String, String>,
+    http_entity::HttpVsn,
+);
 
 #[cfg(test)]
 mod test {
+    use crate::byte_stream::ByteStream;
     use crate::lexer::TokenKind;
 
-    use super::Parser;
+    use super::{ParseErr, Parser};
 
     #[test]
     fn fofoo() {
-        let mut src = "GET /banana".as_bytes().iter().peekable();
-        let mut parser = Parser::new(&mut src);
+        let mut stream = ByteStream::new();
+        stream.read_from(&mut "GET /banana".as_bytes()).unwrap();
+        let mut parser = Parser::new(&mut stream);
 
         let tk = parser.method();
         println!("tk {:?}", tk);
 
         parser.expect(TokenKind::Space).unwrap();
 
-        let path = parser.path(&mut String::new());
-        println!("path {:?}", path);
+        let target = parser.request_target();
+        println!("target {:?}", target);
+    }
+
+    #[test]
+    fn render_underlines_the_offending_span() {
+        let src = b"GET/items HTTP/1.1\r\n";
+        let err = ParseErr::new((3, 4), "Expected Space, found Bad");
+
+        let rendered = err.render(src);
+
+        assert_eq!(
+            rendered,
+            "Expected Space, found Bad\nGET/items HTTP/1.1\r\n   ^"
+        );
+    }
+
+    #[test]
+    fn percent_decodes_hex_digits_regardless_of_letter_case() {
+        let mut stream = ByteStream::new();
+        stream.read_from(&mut "/%2f%2F%41".as_bytes()).unwrap();
+        let mut parser = Parser::new(&mut stream);
+
+        let (path, _) = parser.request_target().unwrap();
+        assert_eq!(path, "///A");
+    }
+
+    #[test]
+    fn percent_decoded_multibyte_utf8_is_reassembled_not_mojibake() {
+        let mut stream = ByteStream::new();
+        // "é" is the two-byte UTF-8 sequence 0xC3 0xA9.
+        stream.read_from(&mut "/%C3%A9".as_bytes()).unwrap();
+        let mut parser = Parser::new(&mut stream);
+
+        let (path, _) = parser.request_target().unwrap();
+        assert_eq!(path, "/é");
+    }
+
+    fn entity_from(input: &str) -> Result<crate::http_entity::HttpEntity, ParseErr> {
+        let mut stream = ByteStream::new();
+        stream.read_from(&mut input.as_bytes()).unwrap();
+        let mut parser = Parser::new(&mut stream);
+        parser.request(&mut std::io::empty())
+    }
+
+    #[test]
+    fn body_reads_exactly_content_length_bytes() {
+        let entity = entity_from("GET / HTTP/1.1\r\nContent-Length: 5\r\n\r\nhello").unwrap();
+        assert_eq!(entity.body, b"hello");
+    }
+
+    #[test]
+    fn body_errors_when_connection_closes_before_content_length_bytes_arrive() {
+        let entity = entity_from("GET / HTTP/1.1\r\nContent-Length: 10\r\n\r\nhi");
+        assert!(entity.is_err());
+    }
+
+    #[test]
+    fn body_rejects_both_content_length_and_chunked_transfer_encoding() {
+        let entity = entity_from(
+            "GET / HTTP/1.1\r\nContent-Length: 5\r\nTransfer-Encoding: chunked\r\n\r\n\
+             5\r\nhello\r\n0\r\n\r\n",
+        );
+        assert!(entity.is_err());
+    }
+
+    #[test]
+    fn chunked_body_reassembles_a_multi_digit_hex_chunk_size() {
+        // A 26-byte first chunk needs two hex digits ("1a"), which is where
+        // the token-based lookahead used to desync from the raw stream.
+        let entity = entity_from(
+            "GET / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n\
+             1a\r\nabcdefghijklmnopqrstuvwxyz\r\n0\r\n\r\n",
+        )
+        .unwrap();
+        assert_eq!(entity.body, b"abcdefghijklmnopqrstuvwxyz");
+    }
+
+    #[test]
+    fn chunked_body_concatenates_multiple_chunks() {
+        let entity = entity_from(
+            "GET / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n\
+             4\r\nWiki\r\n5\r\npedia\r\n0\r\n\r\n",
+        )
+        .unwrap();
+        assert_eq!(entity.body, b"Wikipedia");
+    }
+
+    #[test]
+    fn header_value_with_multibyte_utf8_is_reassembled_not_mojibake() {
+        // "café" ends in the two-byte UTF-8 sequence 0xC3 0xA9, which lexes
+        // as two consecutive Bad tokens that must be decoded together.
+        let entity = entity_from("GET / HTTP/1.1\r\nX-Name: café\r\n\r\n").unwrap();
+        assert_eq!(entity.headers.get("X-Name").map(String::as_str), Some("café"));
+    }
+
+    #[test]
+    fn version_parses_major_and_minor() {
+        let entity = entity_from("GET / HTTP/1.0\r\n\r\n").unwrap();
+        assert_eq!(entity.http_version.major, 1);
+        assert_eq!(entity.http_version.minor, 0);
+
+        let entity = entity_from("GET / HTTP/2.3\r\n\r\n").unwrap();
+        assert_eq!(entity.http_version.major, 2);
+        assert_eq!(entity.http_version.minor, 3);
+    }
+
+    #[test]
+    fn method_parses_the_full_method_set() {
+        use crate::http_entity::HttpMethod;
+
+        let cases = [
+            ("GET", HttpMethod::GET),
+            ("HEAD", HttpMethod::HEAD),
+            ("POST", HttpMethod::POST),
+            ("PUT", HttpMethod::PUT),
+            ("DELETE", HttpMethod::DELETE),
+            ("CONNECT", HttpMethod::CONNECT),
+            ("OPTIONS", HttpMethod::OPTIONS),
+            ("TRACE", HttpMethod::TRACE),
+            ("PATCH", HttpMethod::PATCH),
+        ];
+
+        for (word, expected) in cases {
+            let entity = entity_from(&format!("{word} / HTTP/1.1\r\n\r\n")).unwrap();
+            assert_eq!(
+                format!("{:?}", entity.method),
+                format!("{:?}", expected),
+                "method {word}"
+            );
+        }
+    }
+
+    #[test]
+    fn method_of_an_unknown_word_is_bad() {
+        let entity = entity_from("FROB / HTTP/1.1\r\n\r\n").unwrap();
+        assert!(matches!(entity.method, crate::http_entity::HttpMethod::BAD));
+    }
+
+    #[test]
+    fn invalid_percent_escape_at_buffer_end_is_not_incomplete() {
+        // The '!' is a genuinely bad hex digit, fully present in the
+        // buffer; the fact that nothing follows it (so the next token is
+        // Eof) must not make this look like a truncated request.
+        let mut stream = ByteStream::new();
+        stream.read_from(&mut "/%4!".as_bytes()).unwrap();
+        let mut parser = Parser::new(&mut stream);
+
+        let err = parser.request_target().unwrap_err();
+        assert!(!err.incomplete);
+    }
+
+    #[test]
+    fn query_string_splits_on_ampersand_next_to_tchars() {
+        let mut stream = ByteStream::new();
+        stream.read_from(&mut "/search?q=hello&page=2".as_bytes()).unwrap();
+        let mut parser = Parser::new(&mut stream);
+
+        let (_, query) = parser.request_target().unwrap();
+        assert_eq!(query.get("q").map(String::as_str), Some("hello"));
+        assert_eq!(query.get("page").map(String::as_str), Some("2"));
+    }
+
+    #[test]
+    fn query_value_keeps_a_literal_equals_sign() {
+        let mut stream = ByteStream::new();
+        stream.read_from(&mut "/eval?expr=x=y".as_bytes()).unwrap();
+        let mut parser = Parser::new(&mut stream);
+
+        let (_, query) = parser.request_target().unwrap();
+        assert_eq!(query.get("expr").map(String::as_str), Some("x=y"));
+    }
+
+    #[test]
+    fn invalid_version_digit_at_buffer_end_is_not_incomplete() {
+        let mut stream = ByteStream::new();
+        stream.read_from(&mut "HTTP/1.x".as_bytes()).unwrap();
+        let mut parser = Parser::new(&mut stream);
+
+        let err = parser.version().unwrap_err();
+        assert!(!err.incomplete);
     }
 }
@@ -0,0 +1,102 @@
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use crate::http_entity::HttpEntity;
+use crate::parser::header;
+
+/// An HTTP/1.1 response being assembled before it's written to the wire.
+pub struct HttpResponse {
+    pub status: u16,
+    pub reason: String,
+    pub headers: HashMap<String, String>,
+    pub body: Vec<u8>,
+}
+
+impl HttpResponse {
+    pub fn new(status: u16, reason: impl Into<String>) -> Self {
+        Self {
+            status,
+            reason: reason.into(),
+            headers: HashMap::new(),
+            body: Vec::new(),
+        }
+    }
+
+    /// Shorthand for `HttpResponse::new(200, "OK")`.
+    pub fn ok() -> Self {
+        Self::new(200, "OK")
+    }
+
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.insert(name.into(), value.into());
+        self
+    }
+
+    pub fn body(mut self, body: impl Into<Vec<u8>>) -> Self {
+        self.body = body.into();
+        self
+    }
+
+    /// Serializes the status line, headers, and body with CRLF framing,
+    /// adding a `Content-Length` sized to the body unless the caller already
+    /// set one.
+    pub fn write_to(&self, w: &mut impl Write) -> io::Result<()> {
+        write!(w, "HTTP/1.1 {} {}\r\n", self.status, self.reason)?;
+
+        for (name, value) in &self.headers {
+            write!(w, "{}: {}\r\n", name, value)?;
+        }
+
+        if header(&self.headers, "Content-Length").is_none() {
+            write!(w, "Content-Length: {}\r\n", self.body.len())?;
+        }
+
+        write!(w, "\r\n")?;
+        w.write_all(&self.body)?;
+
+        Ok(())
+    }
+}
+
+/// Produces an [HttpResponse] for a parsed [HttpEntity].
+pub trait Handler {
+    fn handle(&self, entity: &HttpEntity) -> HttpResponse;
+}
+
+#[cfg(test)]
+mod test {
+    use super::HttpResponse;
+
+    fn written(response: HttpResponse) -> String {
+        let mut buf = Vec::new();
+        response.write_to(&mut buf).unwrap();
+        String::from_utf8(buf).unwrap()
+    }
+
+    #[test]
+    fn writes_the_status_line_and_body() {
+        let out = written(HttpResponse::ok().body("hi"));
+        assert!(out.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(out.ends_with("\r\n\r\nhi"));
+    }
+
+    #[test]
+    fn auto_adds_content_length_sized_to_the_body() {
+        let out = written(HttpResponse::ok().body("hello"));
+        assert!(out.contains("Content-Length: 5\r\n"));
+    }
+
+    #[test]
+    fn does_not_override_an_explicit_content_length() {
+        let out = written(HttpResponse::ok().header("Content-Length", "0").body("hello"));
+        assert!(out.contains("Content-Length: 0\r\n"));
+        assert!(!out.contains("Content-Length: 5\r\n"));
+    }
+
+    #[test]
+    fn writes_a_custom_status_and_header() {
+        let out = written(HttpResponse::new(404, "Not Found").header("X-Foo", "bar"));
+        assert!(out.starts_with("HTTP/1.1 404 Not Found\r\n"));
+        assert!(out.contains("X-Foo: bar\r\n"));
+    }
+}